@@ -7,10 +7,123 @@ use bytes::{BytesMut, BufMut};
 pub struct Encoder {
     table: Table,
     size_update: Option<SizeUpdate>,
+    stats: Stats,
+    observer: Option<Box<EncoderObserver>>,
 }
 
 #[derive(Debug)]
 pub enum EncoderError {
+    /// An integer (a table index, string length or dynamic table size
+    /// update) grew too large to fit HPACK's variable-length integer
+    /// encoding.
+    IntegerOverflow,
+    /// The conservative size bound of the first header (or of a pending
+    /// size update) exceeds the caller's `max_len` budget. The bound can be
+    /// larger than the actual encoding, so this can be returned for a
+    /// header that would have fit. Returned only by `encode_to_limit`.
+    BufferFull,
+}
+
+/// Receives a structured record of every decision `Encoder` makes while
+/// encoding a header block.
+///
+/// This mirrors the `header_block` events a QPACK implementation would
+/// write to a qlog stream, and is the only practical way to debug interop
+/// failures where a peer rejects a header block -- otherwise the
+/// representation `encode_header` picks and the size-update merging in
+/// `update_max_size` are entirely opaque to the caller. Set one with
+/// [`Encoder::with_observer`]; with none set, observation compiles out to
+/// a single `if let Some(..)` check per header.
+pub trait EncoderObserver {
+    /// Called once per queued dynamic table size update, after the
+    /// corresponding frame has been written to `dst`.
+    fn on_size_update(&mut self, new_max_size: usize) {
+        let _ = new_max_size;
+    }
+
+    /// Called after a header has been encoded.
+    fn on_header(&mut self, event: &HeaderEvent) {
+        let _ = event;
+    }
+}
+
+/// A structured record of a single header encoding decision, passed to
+/// [`EncoderObserver::on_header`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct HeaderEvent {
+    /// Which `Index` variant the table chose for this header.
+    pub representation: Representation,
+    /// The static or dynamic table index referenced, if any.
+    pub index: Option<usize>,
+    /// Whether encoding this header inserted a new entry into the dynamic
+    /// table.
+    pub table_mutated: bool,
+    /// The number of bytes this header contributed to `dst`.
+    pub bytes_written: usize,
+}
+
+/// The representation `encode_header` chose for a header, mirroring
+/// `hpack::table::Index` without borrowing the `Header` it carries.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Representation {
+    Indexed,
+    Name,
+    Inserted,
+    InsertedValue,
+    NotIndexed,
+}
+
+/// Compression statistics accumulated across calls to `Encoder::encode`.
+///
+/// These counters make decisions that `encode_header` otherwise makes
+/// invisibly -- which representation was chosen, how much the dynamic
+/// table is churning, whether Huffman coding is actually paying off --
+/// observable to the caller, so it can compute an effective compression
+/// ratio or tune `max_size`/capacity.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Stats {
+    /// Headers encoded as a fully indexed field (`Index::Indexed`).
+    pub indexed: usize,
+    /// Headers encoded as a literal with an indexed name that is not added
+    /// to the dynamic table (`Index::Name`), sensitive or not.
+    pub name_indexed: usize,
+    /// Headers encoded as a literal with incremental indexing, inserting a
+    /// new entry into the dynamic table (`Index::Inserted` /
+    /// `Index::InsertedValue`).
+    pub inserted: usize,
+    /// Headers encoded as a literal with a literal name, without indexing
+    /// (`Index::NotIndexed`, not sensitive).
+    pub not_indexed: usize,
+    /// Sensitive headers encoded as a never-indexed literal with a literal
+    /// name (`Index::NotIndexed`). A sensitive header whose name is indexed
+    /// counts under `name_indexed`.
+    pub never_indexed: usize,
+
+    /// Total dynamic table insertions.
+    pub table_insertions: usize,
+    /// Total dynamic table evictions.
+    pub table_evictions: usize,
+
+    /// Uncompressed bytes (header name + value) fed into the encoder.
+    pub bytes_in: usize,
+    /// Compressed header representation bytes written to the destination
+    /// buffer. Dynamic table size update frames are not counted.
+    pub bytes_out: usize,
+
+    /// Strings encoded with Huffman coding.
+    pub huffman_strings: usize,
+    /// Strings encoded as raw literals because Huffman wouldn't shrink them.
+    pub raw_strings: usize,
+}
+
+impl Stats {
+    fn record_string(&mut self, huffman: bool) {
+        if huffman {
+            self.huffman_strings += 1;
+        } else {
+            self.raw_strings += 1;
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -24,9 +137,28 @@ impl Encoder {
         Encoder {
             table: Table::new(max_size, capacity),
             size_update: None,
+            stats: Stats::default(),
+            observer: None,
         }
     }
 
+    /// Sets the observer that receives a structured event for every header
+    /// this encoder encodes. Replaces any previously set observer.
+    pub fn with_observer(mut self, observer: Box<EncoderObserver>) -> Encoder {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Returns the compression statistics accumulated so far.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Resets the accumulated compression statistics to their defaults.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
     /// Queues a max size update.
     ///
     /// The next call to `encode` will include a dynamic size update frame.
@@ -63,68 +195,206 @@ impl Encoder {
     pub fn encode<'a, I>(&mut self, headers: I, dst: &mut BytesMut) -> Result<(), EncoderError>
         where I: IntoIterator<Item=Header>,
     {
+        try!(self.flush_size_update(dst));
+
+        for h in headers {
+            try!(self.encode_header(h, dst));
+        }
+
+        Ok(())
+    }
+
+    /// Encodes as many of `headers` as fit within `max_len` bytes, never
+    /// writing a partial header representation.
+    ///
+    /// Returns the number of headers consumed and whether any remain, so
+    /// the caller can pack the rest into a subsequent CONTINUATION frame.
+    /// If the first header's upper bound (see below) exceeds what is left
+    /// of `max_len`, this returns `EncoderError::BufferFull` rather than
+    /// consuming nothing. A pending dynamic table size update is flushed
+    /// first and counts against `max_len` too: if its upper bound (10 bytes
+    /// a frame) exceeds the budget, this returns `BufferFull` before
+    /// looking at `headers` at all.
+    ///
+    /// Because `self.table.index` both decides a header's representation
+    /// *and* mutates the dynamic table as a side effect, this checks a
+    /// conservative upper bound on the encoded length before calling it,
+    /// so a header that doesn't fit is never touched and encoder/decoder
+    /// table state can't drift apart across the split. The exact encoded
+    /// length is not computed: the bound can exceed it by twenty bytes or
+    /// more, so this can stop before a header that would have fit, or
+    /// return `BufferFull` for a first header that would have fit. It never
+    /// writes past the limit.
+    pub fn encode_to_limit<I>(
+        &mut self,
+        headers: I,
+        dst: &mut BytesMut,
+        max_len: usize,
+    ) -> Result<(usize, bool), EncoderError>
+        where I: IntoIterator<Item=Header>,
+    {
+        if let Some(ref update) = self.size_update {
+            if size_update_upper_bound(update) > max_len {
+                return Err(EncoderError::BufferFull);
+            }
+        }
+
+        try!(self.flush_size_update(dst));
+
+        let start_of_headers = dst.len();
+        let mut consumed = 0;
+
+        for header in headers {
+            let bound = header_upper_bound(&header);
+            let remaining = max_len.saturating_sub(dst.len());
+
+            if bound > remaining {
+                if consumed == 0 && dst.len() == start_of_headers {
+                    return Err(EncoderError::BufferFull);
+                }
+
+                return Ok((consumed, true));
+            }
+
+            try!(self.encode_header(header, dst));
+            consumed += 1;
+        }
+
+        Ok((consumed, false))
+    }
+
+    fn flush_size_update(&mut self, dst: &mut BytesMut) -> Result<(), EncoderError> {
         match self.size_update.take() {
             Some(SizeUpdate::One(val)) => {
+                let before = self.table.len();
                 self.table.resize(val);
-                encode_size_update(val, dst);
+                self.stats.table_evictions += before.saturating_sub(self.table.len());
+                try!(encode_size_update(val, dst));
+
+                if let Some(ref mut observer) = self.observer {
+                    observer.on_size_update(val);
+                }
             }
             Some(SizeUpdate::Two(min, max)) => {
+                let before = self.table.len();
                 self.table.resize(min);
                 self.table.resize(max);
-                encode_size_update(min, dst);
-                encode_size_update(max, dst);
+                self.stats.table_evictions += before.saturating_sub(self.table.len());
+                try!(encode_size_update(min, dst));
+                try!(encode_size_update(max, dst));
+
+                if let Some(ref mut observer) = self.observer {
+                    observer.on_size_update(min);
+                    observer.on_size_update(max);
+                }
             }
             None => {}
         }
 
-        for h in headers {
-            try!(self.encode_header(h, dst));
-        }
-
         Ok(())
     }
 
     fn encode_header(&mut self, header: Header, dst: &mut BytesMut)
         -> Result<(), EncoderError>
     {
+        let start = dst.len();
+        let table_len = self.table.len();
+        let mut inserting = false;
+        let representation;
+        let index;
+
         match self.table.index(header) {
             Index::Indexed(idx, header) => {
                 assert!(!header.is_sensitive());
-                encode_int(idx, 7, 0x80, dst);
+                self.stats.bytes_in += header_bytes(&header);
+                self.stats.indexed += 1;
+                representation = Representation::Indexed;
+                index = Some(idx);
+                try!(encode_int(idx, 7, 0x80, dst));
             }
             Index::Name(idx, header) => {
+                self.stats.bytes_in += header_bytes(&header);
+                self.stats.name_indexed += 1;
+                representation = Representation::Name;
+                index = Some(idx);
+
                 if header.is_sensitive() {
-                    encode_int(idx, 4, 0b10000, dst);
+                    try!(encode_int(idx, 4, 0b10000, dst));
                 } else {
-                    encode_int(idx, 4, 0, dst);
+                    try!(encode_int(idx, 4, 0, dst));
                 }
 
-                encode_str(header.value_slice(), dst);
+                let huffman = try!(encode_str(header.value_slice(), dst));
+                self.stats.record_string(huffman);
             }
             Index::Inserted(header) => {
                 assert!(!header.is_sensitive());
+                self.stats.bytes_in += header_bytes(&header);
+                self.stats.inserted += 1;
+                representation = Representation::Inserted;
+                index = None;
+                inserting = true;
+
                 dst.put_u8(0b01000000);
-                encode_str(header.name().as_slice(), dst);
-                encode_str(header.value_slice(), dst);
+                let huffman = try!(encode_str(header.name().as_slice(), dst));
+                self.stats.record_string(huffman);
+                let huffman = try!(encode_str(header.value_slice(), dst));
+                self.stats.record_string(huffman);
             }
             Index::InsertedValue(idx, header) => {
                 assert!(!header.is_sensitive());
-
-                encode_int(idx, 6, 0b01000000, dst);
-                encode_str(header.value_slice(), dst);
+                self.stats.bytes_in += header_bytes(&header);
+                self.stats.inserted += 1;
+                representation = Representation::InsertedValue;
+                index = Some(idx);
+                inserting = true;
+
+                try!(encode_int(idx, 6, 0b01000000, dst));
+                let huffman = try!(encode_str(header.value_slice(), dst));
+                self.stats.record_string(huffman);
             }
             Index::NotIndexed(header) => {
+                self.stats.bytes_in += header_bytes(&header);
+                representation = Representation::NotIndexed;
+                index = None;
+
                 if header.is_sensitive() {
+                    self.stats.never_indexed += 1;
                     dst.put_u8(0b10000);
                 } else {
+                    self.stats.not_indexed += 1;
                     dst.put_u8(0);
                 }
 
-                encode_str(header.name().as_slice(), dst);
-                encode_str(header.value_slice(), dst);
+                let huffman = try!(encode_str(header.name().as_slice(), dst));
+                self.stats.record_string(huffman);
+                let huffman = try!(encode_str(header.value_slice(), dst));
+                self.stats.record_string(huffman);
             }
         }
 
+        // `Inserted`/`InsertedValue` are the only representations that add
+        // to the dynamic table; everything evicted along the way to make
+        // room for the new entry counts against `table_evictions`.
+        if inserting {
+            self.stats.table_insertions += 1;
+
+            let grew = self.table.len() as isize - table_len as isize;
+            self.stats.table_evictions += (1 - grew).max(0) as usize;
+        }
+
+        let bytes_written = dst.len() - start;
+        self.stats.bytes_out += bytes_written;
+
+        if let Some(ref mut observer) = self.observer {
+            observer.on_header(&HeaderEvent {
+                representation: representation,
+                index: index,
+                table_mutated: inserting,
+                bytes_written: bytes_written,
+            });
+        }
+
         Ok(())
     }
 }
@@ -135,10 +405,24 @@ impl Default for Encoder {
     }
 }
 
-fn encode_str(val: &[u8], dst: &mut BytesMut) {
+/// Encodes `val` as an HPACK string literal, returning whether it was
+/// Huffman-coded (as opposed to written as a raw literal).
+pub(crate) fn encode_str(val: &[u8], dst: &mut BytesMut) -> Result<bool, EncoderError> {
     use std::io::Cursor;
 
-    if val.len() != 0 {
+    if val.len() == 0 {
+        // Write an empty string
+        dst.put_u8(0);
+        return Ok(false);
+    }
+
+    // Huffman coding only pays off when it actually shrinks the value; for
+    // short, high-entropy strings (base64 tokens, digits, opaque ids) the
+    // raw bytes are often smaller. Compare the two before committing either
+    // representation (RFC 7541 section 5.2).
+    let huff_len = huffman::encoded_len(val);
+
+    if huff_len < val.len() {
         let idx = dst.len();
 
         // Push a placeholder byte for the length header
@@ -147,8 +431,6 @@ fn encode_str(val: &[u8], dst: &mut BytesMut) {
         // Encode with huffman
         huffman::encode(val, dst);
 
-        let huff_len = dst.len() - (idx + 1);
-
         if encode_int_one_byte(huff_len, 7) {
             // Write the string head
             dst[idx] = (0x80 | huff_len as u8);
@@ -158,7 +440,7 @@ fn encode_str(val: &[u8], dst: &mut BytesMut) {
 
             let head_len = {
                 let mut head_dst = Cursor::new(&mut buf);
-                encode_int(huff_len, 7, 0x80, &mut head_dst);
+                try!(encode_int(huff_len, 7, 0x80, &mut head_dst));
                 head_dst.position() as usize
             };
 
@@ -177,26 +459,63 @@ fn encode_str(val: &[u8], dst: &mut BytesMut) {
                 dst[idx + i] = buf[i];
             }
         }
+
+        Ok(true)
     } else {
-        // Write an empty string
-        dst.put_u8(0);
+        // Huffman coding wouldn't save anything; emit the literal bytes
+        // with the H bit clear instead.
+        try!(encode_int(val.len(), 7, 0, dst));
+        dst.put_slice(val);
+
+        Ok(false)
     }
 }
 
-fn encode_size_update<B: BufMut>(val: usize, dst: &mut B) {
+fn header_bytes(header: &Header) -> usize {
+    header.name().as_slice().len() + header.value_slice().len()
+}
+
+/// A conservative upper bound on the number of bytes `encode_header` could
+/// ever write for `header`, computed without touching the table. Used by
+/// `Encoder::encode_to_limit` to decide whether a header is safe to
+/// attempt: two prefix bytes, two worst-case variable-length integers (an
+/// index or name length, and a value length, each up to 10 bytes), and the
+/// uncompressed name and value (a literal is never larger than the raw
+/// bytes, see `encode_str`).
+fn header_upper_bound(header: &Header) -> usize {
+    1 + 10 + 1 + 10 + header.name().as_slice().len() + header.value_slice().len()
+}
+
+fn encode_size_update<B: BufMut>(val: usize, dst: &mut B) -> Result<(), EncoderError> {
     encode_int(val, 5, 0b00100000, dst)
 }
 
+/// A conservative upper bound on the number of bytes flushing `update`
+/// could ever write, computed without touching the table. `flush_size_update`
+/// resizes `self.table` as a side effect of writing each frame, so
+/// `encode_to_limit` must check this bound *before* calling it -- the same
+/// reason `header_upper_bound` exists -- or a size update that doesn't fit
+/// the budget would still leave the table resized underneath a write the
+/// caller is about to discard and retry, desyncing the encoder from the
+/// decoder. Each frame is a 5-bit-prefix integer, up to 10 bytes worst case.
+fn size_update_upper_bound(update: &SizeUpdate) -> usize {
+    match *update {
+        SizeUpdate::One(_) => 10,
+        SizeUpdate::Two(..) => 2 * 10,
+    }
+}
+
 /// Encode an integer into the given destination buffer
-fn encode_int<B: BufMut>(
+pub(crate) fn encode_int<B: BufMut>(
     mut value: usize,   // The integer to encode
     prefix_bits: usize, // The number of bits in the prefix
     first_byte: u8,     // The base upon which to start encoding the int
     dst: &mut B)        // The destination buffer
+    -> Result<(), EncoderError>
 {
     if encode_int_one_byte(value, prefix_bits) {
         dst.put_u8(first_byte | value as u8);
-        return;
+        return Ok(());
     }
 
     let low = (1 << prefix_bits) - 1;
@@ -204,7 +523,7 @@ fn encode_int<B: BufMut>(
     value -= low;
 
     if value > 0x0fffffff {
-        panic!("value out of range");
+        return Err(EncoderError::IntegerOverflow);
     }
 
     dst.put_u8(first_byte | low as u8);
@@ -215,10 +534,12 @@ fn encode_int<B: BufMut>(
     }
 
     dst.put_u8(value as u8);
+
+    Ok(())
 }
 
 /// Returns true if the in the int can be fully encoded in the first byte.
-fn encode_int_one_byte(value: usize, prefix_bits: usize) -> bool {
+pub(crate) fn encode_int_one_byte(value: usize, prefix_bits: usize) -> bool {
     value < (1 << prefix_bits) - 1
 }
 
@@ -545,9 +866,190 @@ mod test {
         assert_eq!(&[32 | 31, 69, 0x80 | 62], &res[..]);
     }
 
+    #[test]
+    fn test_encode_str_uses_huffman_when_shorter() {
+        let mut dst = BytesMut::with_capacity(16);
+        encode_str(b"foo", &mut dst).unwrap();
+
+        assert_eq!(0x80 | 2, dst[0]);
+        assert_eq!("foo", huff_decode(&dst[1..]));
+    }
+
+    #[test]
+    fn test_encode_str_uses_raw_when_huffman_is_not_shorter() {
+        let mut dst = BytesMut::with_capacity(16);
+        encode_str(&[0xff], &mut dst).unwrap();
+
+        assert_eq!(&[1, 0xff], &dst[..]);
+    }
+
+    #[test]
+    fn test_stats_track_representation_choices() {
+        let mut encoder = Encoder::default();
+
+        let _ = encode(&mut encoder, vec![method("GET")]);
+        assert_eq!(1, encoder.stats().indexed);
+
+        let _ = encode(&mut encoder, vec![header("foo", "hello")]);
+        assert_eq!(1, encoder.stats().inserted);
+        assert_eq!(1, encoder.stats().table_insertions);
+
+        // Re-encoding the same header now hits the fully indexed path.
+        let _ = encode(&mut encoder, vec![header("foo", "hello")]);
+        assert_eq!(2, encoder.stats().indexed);
+
+        // `content-length` values are never added to the dynamic table, so
+        // they hit the name-indexed literal path instead.
+        let _ = encode(&mut encoder, vec![header("content-length", "1234")]);
+        assert_eq!(1, encoder.stats().name_indexed);
+
+        assert!(encoder.stats().bytes_in > 0);
+        assert!(encoder.stats().bytes_out > 0);
+
+        encoder.reset_stats();
+        assert_eq!(Stats::default(), *encoder.stats());
+    }
+
+    #[test]
+    fn test_stats_track_resize_evictions() {
+        let mut encoder = Encoder::default();
+
+        let _ = encode(&mut encoder, vec![header("foo", "bar")]);
+        let _ = encode(&mut encoder, vec![header("baz", "qux")]);
+        assert_eq!(2, encoder.table.len());
+        assert_eq!(0, encoder.stats().table_evictions);
+
+        // Shrinking the table evicts both entries even though neither was
+        // evicted by an insertion.
+        encoder.update_max_size(0);
+        let _ = encode(&mut encoder, vec![method("GET")]);
+
+        assert_eq!(0, encoder.table.len());
+        assert_eq!(2, encoder.stats().table_evictions);
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        size_updates: Vec<usize>,
+        headers: Vec<HeaderEvent>,
+    }
+
+    impl EncoderObserver for RecordingObserver {
+        fn on_size_update(&mut self, new_max_size: usize) {
+            self.size_updates.push(new_max_size);
+        }
+
+        fn on_header(&mut self, event: &HeaderEvent) {
+            self.headers.push(*event);
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_size_updates_and_header_decisions() {
+        use std::rc::Rc;
+        use std::cell::RefCell;
+
+        struct Shared(Rc<RefCell<RecordingObserver>>);
+
+        impl EncoderObserver for Shared {
+            fn on_size_update(&mut self, new_max_size: usize) {
+                self.0.borrow_mut().on_size_update(new_max_size);
+            }
+
+            fn on_header(&mut self, event: &HeaderEvent) {
+                self.0.borrow_mut().on_header(event);
+            }
+        }
+
+        let recorded = Rc::new(RefCell::new(RecordingObserver::default()));
+        let mut encoder = Encoder::default()
+            .with_observer(Box::new(Shared(recorded.clone())));
+
+        encoder.update_max_size(100);
+        let mut dst = BytesMut::with_capacity(1024);
+        encoder.encode(vec![method("GET")], &mut dst).unwrap();
+
+        let recorded = recorded.borrow();
+        assert_eq!(&[100][..], &recorded.size_updates[..]);
+        assert_eq!(1, recorded.headers.len());
+        assert_eq!(Representation::Indexed, recorded.headers[0].representation);
+        assert_eq!(Some(2), recorded.headers[0].index);
+        assert!(!recorded.headers[0].table_mutated);
+    }
+
     #[test]
     fn test_encoding_into_undersized_buf() {
-        // Test hitting end at multiple points.
+        let mut encoder = Encoder::default();
+        let mut dst = BytesMut::with_capacity(64);
+
+        // The budget only has room for one header's worst-case bound, even
+        // though the second would easily fit in the bytes actually left
+        // over -- `encode_to_limit` never inspects the table to find out,
+        // so it stops conservatively rather than risk a desync.
+        let headers = vec![header("foo", "bar"), header("foo", "bar")];
+        let (consumed, has_more) = encoder.encode_to_limit(headers, &mut dst, 30).unwrap();
+
+        assert_eq!(1, consumed);
+        assert!(has_more);
+        assert!(dst.len() <= 30);
+        assert_eq!(1, encoder.table.len());
+
+        // Handing the remainder to a fresh call, as a caller would for a
+        // CONTINUATION frame, picks up where we left off: the header is
+        // now in the table, so it's fully indexed in a single byte.
+        let mut dst2 = BytesMut::with_capacity(64);
+        let (consumed, has_more) = encoder.encode_to_limit(
+            vec![header("foo", "bar")], &mut dst2, 1024,
+        ).unwrap();
+
+        assert_eq!(1, consumed);
+        assert!(!has_more);
+        assert_eq!(&[0x80 | 62], &dst2[..]);
+        assert_eq!(1, encoder.table.len());
+    }
+
+    #[test]
+    fn test_encoding_single_header_over_its_size_bound_is_buffer_full() {
+        let mut encoder = Encoder::default();
+        let mut dst = BytesMut::with_capacity(64);
+
+        // `foo: bar` encodes to 8 bytes, but its 28-byte upper bound
+        // exceeds the 20-byte budget, so it is rejected untouched.
+        let headers = vec![header("foo", "bar")];
+        let err = encoder.encode_to_limit(headers, &mut dst, 20).unwrap_err();
+
+        match err {
+            EncoderError::BufferFull => {}
+            other => panic!("expected BufferFull, got {:?}", other),
+        }
+
+        assert_eq!(0, encoder.table.len());
+    }
+
+    #[test]
+    fn test_encoding_to_limit_accounts_for_pending_size_update() {
+        let mut encoder = Encoder::default();
+        encoder.update_max_size(4096); // no-op: matches the table's current max size
+        encoder.update_max_size(0);
+        encoder.update_max_size(100); // becomes a size-update pair (0, 100), 3 bytes on the wire
+
+        let mut dst = BytesMut::with_capacity(64);
+        let err = encoder.encode_to_limit(vec![method("GET")], &mut dst, 1)
+            .unwrap_err();
+
+        match err {
+            EncoderError::BufferFull => {}
+            other => panic!("expected BufferFull, got {:?}", other),
+        }
+
+        // The pending update didn't fit, so it must be rejected *before*
+        // `flush_size_update` touches anything -- still pending, the table
+        // untouched, and not a single byte written to `dst` -- so a later
+        // retry with more room re-attempts the same update instead of
+        // silently dropping it or double-resizing the table.
+        assert_eq!(Some(SizeUpdate::Two(0, 100)), encoder.size_update);
+        assert_eq!(4096, encoder.table.max_size());
+        assert_eq!(0, dst.len());
     }
 
     #[test]
@@ -557,7 +1059,7 @@ mod test {
 
     fn encode(e: &mut Encoder, hdrs: Vec<Header>) -> BytesMut {
         let mut dst = BytesMut::with_capacity(1024);
-        e.encode(hdrs, &mut dst);
+        e.encode(hdrs, &mut dst).unwrap();
         dst
     }
 
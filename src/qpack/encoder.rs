@@ -0,0 +1,687 @@
+use super::instruction::Instruction;
+use super::table;
+
+use hpack::{huffman, Header};
+use hpack::encoder::{encode_int, encode_str};
+use hpack::encoder::EncoderError as HpackError;
+
+use bytes::{BytesMut, BufMut};
+
+/// Encodes header lists into QPACK field line representations.
+///
+/// An `Encoder` owns two output streams: the header block that is sent
+/// with the request/response (see [`encode`]) and the encoder instruction
+/// stream that carries dynamic table mutations (see [`drain_instructions`]).
+/// The two must reach the peer independently -- the header block rides on
+/// the HTTP/3 request stream, the instructions on the dedicated encoder
+/// stream -- which is why they are produced into separate buffers instead
+/// of being interleaved the way HPACK interleaves size updates into
+/// `encode`.
+///
+/// [`encode`]: Encoder::encode
+/// [`drain_instructions`]: Encoder::drain_instructions
+pub struct Encoder {
+    mode: Mode,
+    capacity: usize,
+
+    /// Dynamic table entries, oldest first. The absolute index of
+    /// `inserted[i]` is `base_index + i`.
+    inserted: Vec<(Vec<u8>, Vec<u8>)>,
+    base_index: usize,
+
+    /// Instructions queued for the encoder stream but not yet drained.
+    pending: Vec<Instruction>,
+
+    /// The number of dynamic table insertions the decoder has acknowledged
+    /// receiving (via Section Acknowledgment / Insert Count Increment).
+    /// Entries below this index are always safe to reference; a block
+    /// references an entry at or above it, which the decoder might not
+    /// have applied yet, only while `max_blocked_streams` leaves room (see
+    /// `can_reference`).
+    known_received_count: usize,
+
+    /// The number of header blocks emitted whose Required Insert Count was
+    /// above `known_received_count` when they were emitted, i.e. streams
+    /// that may be blocked until the decoder catches up. Only
+    /// `stream_unblocked` lowers it; `ack_received` does not. Bounded by
+    /// `Mode::Dynamic`'s `max_blocked_streams`.
+    blocked_streams: usize,
+}
+
+/// Controls whether the encoder may use the dynamic table at all.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    /// Only ever reference the static table. This is the simplest mode to
+    /// integrate: there is no encoder stream to run, no risk of blocking a
+    /// stream on an unacknowledged insertion, and no Section
+    /// Acknowledgment bookkeeping to track.
+    StaticOnly,
+
+    /// Use the dynamic table, subject to `max_blocked_streams`.
+    Dynamic { max_blocked_streams: usize },
+}
+
+#[derive(Debug)]
+pub enum EncoderError {
+    /// The dynamic table capacity is smaller than the entry being
+    /// inserted, so it can never fit. `encode` does not return this: it
+    /// writes such a header as a literal instead.
+    EntryTooLarge,
+    /// Surfaced from the shared HPACK integer/string coders (e.g. an
+    /// index or length that overflowed the variable-length integer
+    /// encoding).
+    Hpack(HpackError),
+}
+
+impl From<HpackError> for EncoderError {
+    fn from(err: HpackError) -> EncoderError {
+        EncoderError::Hpack(err)
+    }
+}
+
+impl Encoder {
+    pub fn new(capacity: usize, mode: Mode) -> Encoder {
+        let mut encoder = Encoder {
+            mode: mode,
+            capacity: capacity,
+            inserted: Vec::new(),
+            base_index: 0,
+            pending: Vec::new(),
+            known_received_count: 0,
+            blocked_streams: 0,
+        };
+
+        // RFC 9204 section 3.2.3: the decoder's dynamic table capacity
+        // starts at zero until this instruction raises it, so it has to be
+        // queued at least once up front -- otherwise the very first
+        // `Insert*` instruction below would be applied against a table the
+        // decoder thinks has no room at all. `Mode::StaticOnly` never
+        // inserts, so it queues nothing and needs no encoder stream.
+        if mode != Mode::StaticOnly {
+            encoder.pending.push(Instruction::SetDynamicTableCapacity(capacity));
+        }
+
+        encoder
+    }
+
+    /// Returns an encoder restricted to the static table, as described by
+    /// `Mode::StaticOnly`.
+    pub fn static_only() -> Encoder {
+        Encoder::new(0, Mode::StaticOnly)
+    }
+
+    /// The number of entries the decoder has acknowledged, i.e. the
+    /// "draining index" below which dynamic table references are always
+    /// safe to emit.
+    pub fn known_received_count(&self) -> usize {
+        self.known_received_count
+    }
+
+    /// Called when a Section Acknowledgment or Insert Count Increment
+    /// decoder instruction arrives, advancing the draining index.
+    pub fn ack_received(&mut self, count: usize) {
+        if count > self.known_received_count {
+            self.known_received_count = count;
+        }
+    }
+
+    /// Called when a Section Acknowledgment or Stream Cancellation
+    /// instruction resolves a stream that had been counted against
+    /// `max_blocked_streams` by a previous `encode` call.
+    pub fn stream_unblocked(&mut self) {
+        self.blocked_streams = self.blocked_streams.saturating_sub(1);
+    }
+
+    /// Whether referencing the dynamic table entry at absolute index
+    /// `abs_idx` is safe right now: either the decoder has already
+    /// acknowledged it, or this encoder still has room in its
+    /// `max_blocked_streams` budget to risk blocking a stream on it.
+    fn can_reference(&self, abs_idx: usize) -> bool {
+        if abs_idx < self.known_received_count {
+            return true;
+        }
+
+        match self.mode {
+            Mode::StaticOnly => false,
+            Mode::Dynamic { max_blocked_streams } => self.blocked_streams < max_blocked_streams,
+        }
+    }
+
+    /// Encodes `headers` into a single field section.
+    ///
+    /// Returns the Required Insert Count the decoder must observe before
+    /// it may process the block. This method has already written it,
+    /// together with `Base`, as the block's prefix (RFC 9204 section
+    /// 4.5.1); the caller does not need to encode it again.
+    ///
+    /// A reference to an entry the decoder hasn't acknowledged yet would
+    /// block the stream until it catches up, so such references are only
+    /// emitted while `Mode::Dynamic`'s `max_blocked_streams` budget has
+    /// room; once it's exhausted, this falls back to a literal with no
+    /// dynamic table dependency instead.
+    pub fn encode<I>(&mut self, headers: I, dst: &mut BytesMut) -> Result<usize, EncoderError>
+        where I: IntoIterator<Item = Header>
+    {
+        // Base is fixed to the dynamic table's state as of the start of
+        // this call. Entries already at or above it were inserted earlier
+        // and are referenced relative to Base (`Base - 1 - idx`); entries
+        // this call itself inserts land at or above Base and are
+        // referenced post-base (`idx - Base`) instead.
+        let base = self.base_index + self.inserted.len();
+        let mut required_insert_count = 0;
+        let mut block = BytesMut::with_capacity(64);
+
+        for header in headers {
+            let name = header.name().as_slice().to_vec();
+            let value = header.value_slice().to_vec();
+            let sensitive = header.is_sensitive();
+
+            if let Some((idx, exact)) = table::lookup(
+                ::std::str::from_utf8(&name).unwrap_or(""),
+                ::std::str::from_utf8(&value).ok(),
+            ) {
+                // A sensitive header never takes the fully-indexed path --
+                // the static table never predicts a sensitive value, so
+                // `exact && sensitive` shouldn't occur in practice, but
+                // falling through to the dynamic table and literal paths
+                // after this block, which never index a sensitive value,
+                // is the safe choice if it ever did.
+                if exact && !sensitive {
+                    try!(encode_indexed(idx, true, &mut block));
+                    continue;
+                }
+
+                if !exact {
+                    // The name is already indexed, just not with this
+                    // value. If an earlier insertion of this exact header
+                    // can be referenced, it is fully indexed from the
+                    // dynamic table; otherwise inserting it lets future
+                    // occurrences take that path, and `try_insert`
+                    // references the static name instead of
+                    // retransmitting it.
+                    if !sensitive {
+                        if let Some(dyn_idx) = self.find_dynamic(&name, &value) {
+                            if self.can_reference(dyn_idx) {
+                                required_insert_count = required_insert_count.max(dyn_idx + 1);
+                                try!(self.reference_indexed(dyn_idx, base, &mut block));
+                                continue;
+                            }
+                        }
+                    }
+
+                    let next_idx = self.base_index + self.inserted.len();
+
+                    if !sensitive && self.can_reference(next_idx) && self.try_insert(&name, &value) {
+                        required_insert_count = required_insert_count.max(next_idx + 1);
+                        try!(encode_indexed_post_base(next_idx - base, &mut block));
+                    } else {
+                        try!(encode_literal_with_name_reference(idx, true, sensitive, &value, &mut block));
+                    }
+
+                    continue;
+                }
+            }
+
+            // Sensitive headers never match or contribute a full dynamic
+            // table value -- that could only succeed by having indexed
+            // this exact sensitive value before, which the insertion guard
+            // below prevents -- mirroring the HPACK encoder's protection.
+            if !sensitive {
+                if let Some(dyn_idx) = self.find_dynamic(&name, &value) {
+                    if self.can_reference(dyn_idx) {
+                        required_insert_count = required_insert_count.max(dyn_idx + 1);
+                        try!(self.reference_indexed(dyn_idx, base, &mut block));
+                        continue;
+                    }
+                }
+            }
+
+            if let Some(dyn_idx) = self.find_dynamic_name(&name) {
+                if self.can_reference(dyn_idx) {
+                    required_insert_count = required_insert_count.max(dyn_idx + 1);
+                    try!(self.reference_name(dyn_idx, base, sensitive, &value, &mut block));
+                    continue;
+                }
+            }
+
+            let next_idx = self.base_index + self.inserted.len();
+
+            // Sensitive headers are never inserted into the dynamic table.
+            if !sensitive && self.can_reference(next_idx) && self.try_insert(&name, &value) {
+                required_insert_count = required_insert_count.max(next_idx + 1);
+                try!(encode_indexed_post_base(next_idx - base, &mut block));
+            } else {
+                try!(encode_literal_with_literal_name(&name, &value, sensitive, &mut block));
+            }
+        }
+
+        // Every reference above was already gated by `can_reference`, so
+        // `required_insert_count` only ever names insertions this encoder
+        // was willing to risk blocking on.
+        if required_insert_count > self.known_received_count {
+            self.blocked_streams += 1;
+        }
+
+        try!(encode_prefix(required_insert_count, base, dst));
+        dst.put_slice(&block);
+
+        Ok(required_insert_count)
+    }
+
+    /// Drains and returns the encoder instructions accumulated since the
+    /// last call, for the caller to write onto the encoder stream.
+    pub fn drain_instructions(&mut self, dst: &mut BytesMut) -> Result<(), EncoderError> {
+        for instruction in self.pending.drain(..) {
+            try!(instruction.encode(dst));
+        }
+
+        Ok(())
+    }
+
+    fn find_dynamic(&self, name: &[u8], value: &[u8]) -> Option<usize> {
+        self.inserted.iter().enumerate()
+            .find(|&(_, &(ref n, ref v))| n == name && v == value)
+            .map(|(i, _)| self.base_index + i)
+    }
+
+    fn find_dynamic_name(&self, name: &[u8]) -> Option<usize> {
+        self.inserted.iter().enumerate()
+            .find(|&(_, &(ref n, _))| n == name)
+            .map(|(i, _)| self.base_index + i)
+    }
+
+    /// Encodes a reference to the dynamic table entry at absolute index
+    /// `dyn_idx`, choosing pre-base or post-base framing depending on
+    /// whether it was already in the table as of `base` or was inserted by
+    /// this same call.
+    fn reference_indexed(&self, dyn_idx: usize, base: usize, block: &mut BytesMut) -> Result<(), EncoderError> {
+        if dyn_idx < base {
+            encode_indexed(base - 1 - dyn_idx, false, block)
+        } else {
+            encode_indexed_post_base(dyn_idx - base, block)
+        }
+    }
+
+    /// Like `reference_indexed`, but for a literal whose *name* (not value)
+    /// matches the dynamic table entry at `dyn_idx`.
+    fn reference_name(&self, dyn_idx: usize, base: usize, sensitive: bool, value: &[u8], block: &mut BytesMut)
+        -> Result<(), EncoderError>
+    {
+        if dyn_idx < base {
+            encode_literal_with_name_reference(base - 1 - dyn_idx, false, sensitive, value, block)
+        } else {
+            encode_literal_post_base_name_reference(dyn_idx - base, sensitive, value, block)
+        }
+    }
+
+    fn try_insert(&mut self, name: &[u8], value: &[u8]) -> bool {
+        if let Mode::StaticOnly = self.mode {
+            return false;
+        }
+
+        let entry_size = name.len() + value.len() + 32;
+
+        if entry_size > self.capacity {
+            return false;
+        }
+
+        while self.size() + entry_size > self.capacity {
+            self.inserted.remove(0);
+            self.base_index += 1;
+        }
+
+        // Prefer referencing a name the peer already has -- static table
+        // first, since it costs nothing to check and never evicts -- over
+        // retransmitting it as a literal.
+        let next_idx = self.base_index + self.inserted.len();
+        let instruction = if let Some((idx, _)) = table::lookup(
+            ::std::str::from_utf8(name).unwrap_or(""),
+            None,
+        ) {
+            Instruction::InsertWithNameReference {
+                is_static: true,
+                name_idx: idx,
+                value: value.to_vec(),
+            }
+        } else if let Some(dyn_idx) = self.find_dynamic_name(name) {
+            // Relative indexing on the encoder stream (RFC 9204 section
+            // 3.2.4) counts back from the most recently inserted entry,
+            // which is always at relative index 0 -- unlike the Base-
+            // relative indexing `reference_name` uses for field lines.
+            Instruction::InsertWithNameReference {
+                is_static: false,
+                name_idx: next_idx - 1 - dyn_idx,
+                value: value.to_vec(),
+            }
+        } else {
+            Instruction::InsertWithLiteralName {
+                name: name.to_vec(),
+                value: value.to_vec(),
+            }
+        };
+
+        self.pending.push(instruction);
+        self.inserted.push((name.to_vec(), value.to_vec()));
+
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.inserted.iter().map(|&(ref n, ref v)| n.len() + v.len() + 32).sum()
+    }
+}
+
+/// `Indexed Field Line` (`1Txxxxxx`, static when `is_static`).
+fn encode_indexed(idx: usize, is_static: bool, dst: &mut BytesMut) -> Result<(), EncoderError> {
+    let first_byte = if is_static { 0b11000000 } else { 0b10000000 };
+    Ok(try!(encode_int(idx, 6, first_byte, dst)))
+}
+
+/// `Indexed Field Line With Post-Base Index` (`0001xxxx`).
+fn encode_indexed_post_base(idx: usize, dst: &mut BytesMut) -> Result<(), EncoderError> {
+    Ok(try!(encode_int(idx, 4, 0b00010000, dst)))
+}
+
+/// `Literal Field Line With Name Reference` (`01NTxxxx`).
+fn encode_literal_with_name_reference(
+    idx: usize,
+    is_static: bool,
+    sensitive: bool,
+    value: &[u8],
+    dst: &mut BytesMut,
+) -> Result<(), EncoderError> {
+    let mut first_byte = 0b01000000;
+    if sensitive { first_byte |= 0b00100000; }
+    if is_static { first_byte |= 0b00010000; }
+    try!(encode_int(idx, 4, first_byte, dst));
+    try!(encode_str(value, dst));
+    Ok(())
+}
+
+/// `Literal Field Line With Post-Base Name Reference` (`0000Nxxx`).
+fn encode_literal_post_base_name_reference(idx: usize, sensitive: bool, value: &[u8], dst: &mut BytesMut)
+    -> Result<(), EncoderError>
+{
+    let first_byte = if sensitive { 0b00001000 } else { 0 };
+    try!(encode_int(idx, 3, first_byte, dst));
+    try!(encode_str(value, dst));
+    Ok(())
+}
+
+/// `Literal Field Line With Literal Name` (`001NHxxx`, a 3-bit name length
+/// prefix).
+fn encode_literal_with_literal_name(name: &[u8], value: &[u8], sensitive: bool, dst: &mut BytesMut)
+    -> Result<(), EncoderError>
+{
+    let tag = if sensitive { 0b00110000 } else { 0b00100000 };
+    try!(encode_packed_str(tag, 3, name, dst));
+    try!(encode_str(value, dst));
+    Ok(())
+}
+
+/// Encodes `val` as a QPACK string whose Huffman flag and length prefix
+/// share the *same* byte as the representation's other tag bits (e.g.
+/// `Literal Field Line With Literal Name`'s `001NHxxx` or `Insert With
+/// Literal Name`'s `01Hxxxxx`), unlike HPACK's `encode_str`, which always
+/// starts the string on a fresh byte. `tag` is the first byte with the H
+/// bit and length left as zero; `prefix_bits` is how many low bits of
+/// `tag` are available for the length.
+pub(crate) fn encode_packed_str(tag: u8, prefix_bits: usize, val: &[u8], dst: &mut BytesMut) -> Result<(), HpackError> {
+    let huff_len = huffman::encoded_len(val);
+
+    if huff_len < val.len() {
+        let h_bit = 1u8 << prefix_bits;
+        try!(encode_int(huff_len, prefix_bits, tag | h_bit, dst));
+        huffman::encode(val, dst);
+    } else {
+        try!(encode_int(val.len(), prefix_bits, tag, dst));
+        dst.put_slice(val);
+    }
+
+    Ok(())
+}
+
+/// Encodes the Required Insert Count and Base that prefix every QPACK
+/// header block (RFC 9204 section 4.5.1). Both use the QPACK integer
+/// representation; the sign bit on `Base`'s prefix records whether Base is
+/// smaller than the Required Insert Count, in which case the delta written
+/// is `Required Insert Count - Base - 1`.
+fn encode_prefix(required_insert_count: usize, base: usize, dst: &mut BytesMut) -> Result<(), EncoderError> {
+    let encoded_ric = if required_insert_count == 0 {
+        0
+    } else {
+        required_insert_count + 1
+    };
+    try!(encode_int(encoded_ric, 8, 0, dst));
+
+    if base >= required_insert_count {
+        try!(encode_int(base - required_insert_count, 7, 0, dst));
+    } else {
+        try!(encode_int(required_insert_count - base - 1, 7, 0b10000000, dst));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http::header::{HeaderName, HeaderValue};
+
+    #[test]
+    fn test_inserting_multiple_headers_in_one_call_uses_distinct_post_base_indices() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 16 });
+        encoder.ack_received(0);
+
+        let mut dst = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(
+            vec![header("x-a", "1"), header("x-b", "2")],
+            &mut dst,
+        ).unwrap();
+
+        // Both headers are brand new names, so both get inserted; the
+        // second insertion must reference post-base index 1, not 0 again.
+        assert_eq!(2, required_insert_count);
+        assert_eq!(2, encoder.inserted.len());
+
+        // `Indexed Field Line With Post-Base Index` (`0001xxxx`) for
+        // indices 0 and 1, right after the 2-byte block prefix.
+        assert_eq!(&[0b00010000, 0b00010001], &dst[2..]);
+    }
+
+    #[test]
+    fn test_referencing_an_already_acked_entry_uses_pre_base_indexing() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 16 });
+
+        let mut dst = BytesMut::with_capacity(64);
+        encoder.encode(vec![header("x-a", "1")], &mut dst).unwrap();
+        encoder.ack_received(1);
+
+        // A second, unrelated call references the entry inserted above.
+        // Base has now moved past it, so it must be encoded pre-base
+        // (`Indexed Field Line`, dynamic), not post-base.
+        let mut dst2 = BytesMut::with_capacity(64);
+        encoder.encode(vec![header("x-a", "1")], &mut dst2).unwrap();
+
+        // `Indexed Field Line` (dynamic, tag `10xxxxxx`) comes immediately
+        // after the two-byte Required Insert Count/Base prefix.
+        assert_eq!(0b10000000, dst2[2] & 0b11000000);
+    }
+
+    #[test]
+    fn test_blocked_stream_budget_falls_back_to_literal_when_exhausted() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 0 });
+
+        let mut dst = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(vec![header("x-a", "1")], &mut dst).unwrap();
+
+        // No budget to risk blocking a stream on an unacknowledged
+        // insertion, so the header falls back to a literal instead of
+        // being added to the dynamic table.
+        assert_eq!(0, required_insert_count);
+        assert_eq!(0, encoder.inserted.len());
+    }
+
+    #[test]
+    fn test_second_blocked_stream_falls_back_until_unblocked() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 1 });
+
+        let mut dst1 = BytesMut::with_capacity(64);
+        encoder.encode(vec![header("x-a", "1")], &mut dst1).unwrap();
+        assert_eq!(1, encoder.inserted.len());
+
+        // The budget is already spent on the first stream; a second new
+        // header must not insert (and block) another.
+        let mut dst2 = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(vec![header("x-b", "2")], &mut dst2).unwrap();
+        assert_eq!(0, required_insert_count);
+        assert_eq!(1, encoder.inserted.len());
+
+        encoder.stream_unblocked();
+
+        let mut dst3 = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(vec![header("x-c", "3")], &mut dst3).unwrap();
+        assert_eq!(2, required_insert_count);
+        assert_eq!(2, encoder.inserted.len());
+    }
+
+    #[test]
+    fn test_sensitive_headers_are_never_inserted_or_indexed() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 16 });
+
+        let mut dst = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(
+            vec![sensitive_header("my-password", "hunter2")],
+            &mut dst,
+        ).unwrap();
+
+        assert_eq!(0, required_insert_count);
+        assert_eq!(0, encoder.inserted.len());
+
+        // `Literal Field Line With Literal Name`, never-indexed (N bit
+        // set): tag `001N` with N=1 right after the 2-byte block prefix.
+        assert_eq!(0b00110000, dst[2] & 0b11110000);
+
+        // Encoding the exact same sensitive header again must not hit an
+        // indexed path -- there is nothing in either table to index it
+        // against, since it was never inserted.
+        let mut dst2 = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(
+            vec![sensitive_header("my-password", "hunter2")],
+            &mut dst2,
+        ).unwrap();
+
+        assert_eq!(0, required_insert_count);
+        assert_eq!(0, encoder.inserted.len());
+        assert_eq!(0b00110000, dst2[2] & 0b11110000);
+    }
+
+    #[test]
+    fn test_sensitive_header_with_static_name_reuses_name_not_value() {
+        let mut encoder = Encoder::static_only();
+
+        let mut dst = BytesMut::with_capacity(64);
+        encoder.encode(vec![sensitive_header("authorization", "secret")], &mut dst).unwrap();
+
+        // `Literal Field Line With Name Reference`, static + never-indexed:
+        // tag `01` with N=1, T=1.
+        assert_eq!(0b01110000, dst[2] & 0b11110000);
+    }
+
+    #[test]
+    fn test_static_table_exact_match_is_fully_indexed() {
+        let mut encoder = Encoder::static_only();
+
+        let mut dst = BytesMut::with_capacity(64);
+        let required_insert_count = encoder.encode(vec![header(":path", "/")], &mut dst).unwrap();
+
+        assert_eq!(0, required_insert_count);
+
+        // `Indexed Field Line`, static (`1T......` with T=1), right after
+        // the 2-byte block prefix; index 1 is `:path: /` (see table.rs).
+        assert_eq!(&[0b11000001], &dst[2..]);
+    }
+
+    #[test]
+    fn test_unknown_header_is_a_literal_with_literal_name() {
+        let mut encoder = Encoder::static_only();
+
+        let mut dst = BytesMut::with_capacity(64);
+        encoder.encode(vec![header("x-custom", "v")], &mut dst).unwrap();
+
+        // `Literal Field Line With Literal Name`, not never-indexed: tag
+        // `001N` with N=0, right after the 2-byte block prefix.
+        assert_eq!(0b00100000, dst[2] & 0b11110000);
+    }
+
+    #[test]
+    fn test_new_queues_a_set_dynamic_table_capacity_instruction() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 16 });
+
+        assert_eq!(vec![Instruction::SetDynamicTableCapacity(4096)], encoder.pending);
+
+        let mut dst = BytesMut::with_capacity(16);
+        encoder.drain_instructions(&mut dst).unwrap();
+        assert!(encoder.pending.is_empty());
+    }
+
+    #[test]
+    fn test_inserting_a_known_static_name_references_it_instead_of_a_literal() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 16 });
+        encoder.drain_instructions(&mut BytesMut::with_capacity(16)).unwrap();
+
+        let mut dst = BytesMut::with_capacity(64);
+        // ":path" is a static table name (index 1), but "/custom" isn't
+        // its static value, so this still inserts a new entry -- just
+        // referencing the static name instead of retransmitting it.
+        encoder.encode(vec![header(":path", "/custom")], &mut dst).unwrap();
+
+        assert_eq!(
+            vec![Instruction::InsertWithNameReference {
+                is_static: true,
+                name_idx: 1,
+                value: b"/custom".to_vec(),
+            }],
+            encoder.pending,
+        );
+    }
+
+    #[test]
+    fn test_inserting_a_known_dynamic_name_references_it_instead_of_a_literal() {
+        let mut encoder = Encoder::new(4096, Mode::Dynamic { max_blocked_streams: 16 });
+        encoder.drain_instructions(&mut BytesMut::with_capacity(16)).unwrap();
+
+        assert!(encoder.try_insert(b"x-a", b"1"));
+        encoder.pending.clear();
+
+        assert!(encoder.try_insert(b"x-a", b"2"));
+
+        // Relative indexing on the encoder stream (RFC 9204 section 3.2.4)
+        // counts back from the most recently inserted entry -- relative
+        // index 0 here -- unlike the Base-relative indexing a field line
+        // reference uses.
+        assert_eq!(
+            vec![Instruction::InsertWithNameReference {
+                is_static: false,
+                name_idx: 0,
+                value: b"2".to_vec(),
+            }],
+            encoder.pending,
+        );
+    }
+
+    fn header(name: &str, val: &str) -> Header {
+        let name = HeaderName::from_bytes(name.as_bytes()).unwrap();
+        let value = HeaderValue::try_from_bytes(val.as_bytes()).unwrap();
+
+        Header::Field { name: name, value: value }
+    }
+
+    fn sensitive_header(name: &str, val: &str) -> Header {
+        let name = HeaderName::from_bytes(name.as_bytes()).unwrap();
+        let mut value = HeaderValue::try_from_bytes(val.as_bytes()).unwrap();
+        value.set_sensitive(true);
+
+        Header::Field { name: name, value: value }
+    }
+}
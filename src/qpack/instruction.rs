@@ -0,0 +1,78 @@
+use super::encoder::encode_packed_str;
+
+use hpack::encoder::{encode_int, encode_str, EncoderError};
+
+use bytes::BytesMut;
+
+/// An instruction sent on the (unidirectional) QPACK encoder stream.
+///
+/// Dynamic table mutations are never interleaved with the header block
+/// that uses them; they travel ahead of time on this side channel so the
+/// decoder can apply them as soon as they arrive, independent of the
+/// order in which header blocks show up on request streams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    /// `Set Dynamic Table Capacity` (`001xxxxx`)
+    SetDynamicTableCapacity(usize),
+
+    /// `Insert With Name Reference` (`1Txxxxxx`)
+    ///
+    /// `is_static` selects whether `name_idx` refers to the static or the
+    /// dynamic table.
+    InsertWithNameReference {
+        is_static: bool,
+        name_idx: usize,
+        value: Vec<u8>,
+    },
+
+    /// `Insert With Literal Name` (`01Hxxxxx`, a 5-bit name length prefix)
+    InsertWithLiteralName { name: Vec<u8>, value: Vec<u8> },
+
+    /// `Duplicate` (`000xxxxx`)
+    Duplicate(usize),
+}
+
+impl Instruction {
+    /// Encodes `self` onto the encoder stream buffer.
+    pub(crate) fn encode(&self, dst: &mut BytesMut) -> Result<(), EncoderError> {
+        match *self {
+            Instruction::SetDynamicTableCapacity(cap) => {
+                try!(encode_int(cap, 5, 0b00100000, dst));
+            }
+            Instruction::InsertWithNameReference { is_static, name_idx, ref value } => {
+                let first_byte = if is_static { 0b11000000 } else { 0b10000000 };
+                try!(encode_int(name_idx, 6, first_byte, dst));
+                try!(encode_str(value, dst));
+            }
+            Instruction::InsertWithLiteralName { ref name, ref value } => {
+                try!(encode_packed_str(0b01000000, 5, name, dst));
+                try!(encode_str(value, dst));
+            }
+            Instruction::Duplicate(idx) => {
+                try!(encode_int(idx, 5, 0, dst));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert_with_literal_name_packs_tag_into_length_byte() {
+        let instr = Instruction::InsertWithLiteralName {
+            name: b"x-a".to_vec(),
+            value: b"1".to_vec(),
+        };
+
+        let mut dst = BytesMut::with_capacity(16);
+        instr.encode(&mut dst).unwrap();
+
+        // Tag bits `01` plus the 5-bit name length prefix share one byte,
+        // not two separate bytes the way HPACK's `encode_str` would emit.
+        assert_eq!(0b01000000, dst[0] & 0b11000000);
+    }
+}
@@ -0,0 +1,28 @@
+//! An implementation of QPACK, the header compression format used by
+//! HTTP/3 (RFC 9204).
+//!
+//! QPACK is deliberately similar to HPACK: it reuses the same Huffman
+//! code and the same variable-length integer encoding (see
+//! `hpack::encoder::{encode_int, encode_str}`). The two differ in how a
+//! header block is framed and how the dynamic table is updated:
+//!
+//! * Field line representations carry a different set of prefixes
+//!   (indexed, indexed-post-base, literal-with-name-reference,
+//!   literal-with-literal-name, literal-post-base) because QPACK streams
+//!   can arrive out of order and a representation may need to reference
+//!   an entry inserted *after* the base of the block.
+//! * Dynamic table mutations never appear inline in a header block; they
+//!   are sent ahead of time on a dedicated unidirectional encoder stream
+//!   as one of the [`instruction::Instruction`] variants.
+//! * Every header block opens with a "Required Insert Count" and "Base"
+//!   prefix so the decoder knows how many dynamic table insertions it
+//!   must have observed before the block can be processed.
+
+mod encoder;
+mod instruction;
+mod table;
+
+pub use self::encoder::{Encoder, EncoderError, Mode};
+pub use self::instruction::Instruction;
+
+pub(crate) use self::table::STATIC_TABLE;
@@ -0,0 +1,154 @@
+/// The QPACK static table (RFC 9204, Appendix A).
+///
+/// Unlike HPACK's 61-entry static table, QPACK's has 99 entries and is
+/// indexed from zero. A `None` value means the entry only predicts a
+/// header *name*; the value must still be encoded as a literal.
+pub(crate) static STATIC_TABLE: &'static [(&'static str, Option<&'static str>)] = &[
+    (":authority", None),                                                     // 0
+    (":path", Some("/")),                                                     // 1
+    ("age", Some("0")),                                                       // 2
+    ("content-disposition", None),                                           // 3
+    ("content-length", Some("0")),                                           // 4
+    ("cookie", None),                                                         // 5
+    ("date", None),                                                          // 6
+    ("etag", None),                                                         // 7
+    ("if-modified-since", None),                                             // 8
+    ("if-none-match", None),                                                 // 9
+    ("last-modified", None),                                                 // 10
+    ("link", None),                                                          // 11
+    ("location", None),                                                      // 12
+    ("referer", None),                                                       // 13
+    ("set-cookie", None),                                                    // 14
+    (":method", Some("CONNECT")),                                            // 15
+    (":method", Some("DELETE")),                                             // 16
+    (":method", Some("GET")),                                                // 17
+    (":method", Some("HEAD")),                                               // 18
+    (":method", Some("OPTIONS")),                                            // 19
+    (":method", Some("POST")),                                               // 20
+    (":method", Some("PUT")),                                                // 21
+    (":scheme", Some("http")),                                               // 22
+    (":scheme", Some("https")),                                              // 23
+    (":status", Some("103")),                                                // 24
+    (":status", Some("200")),                                                // 25
+    (":status", Some("304")),                                                // 26
+    (":status", Some("404")),                                                // 27
+    (":status", Some("503")),                                                // 28
+    ("accept", Some("*/*")),                                                 // 29
+    ("accept", Some("application/dns-message")),                             // 30
+    ("accept-encoding", Some("gzip, deflate, br")),                          // 31
+    ("accept-ranges", Some("bytes")),                                        // 32
+    ("access-control-allow-headers", Some("cache-control")),                 // 33
+    ("access-control-allow-headers", Some("content-type")),                  // 34
+    ("access-control-allow-origin", Some("*")),                              // 35
+    ("cache-control", Some("max-age=0")),                                    // 36
+    ("cache-control", Some("max-age=2592000")),                              // 37
+    ("cache-control", Some("max-age=604800")),                               // 38
+    ("cache-control", Some("no-cache")),                                     // 39
+    ("cache-control", Some("no-store")),                                     // 40
+    ("cache-control", Some("public, max-age=31536000")),                     // 41
+    ("content-encoding", Some("br")),                                        // 42
+    ("content-encoding", Some("gzip")),                                      // 43
+    ("content-type", Some("application/dns-message")),                      // 44
+    ("content-type", Some("application/javascript")),                       // 45
+    ("content-type", Some("application/json")),                              // 46
+    ("content-type", Some("application/x-www-form-urlencoded")),            // 47
+    ("content-type", Some("image/gif")),                                    // 48
+    ("content-type", Some("image/jpeg")),                                   // 49
+    ("content-type", Some("image/png")),                                    // 50
+    ("content-type", Some("text/css")),                                     // 51
+    ("content-type", Some("text/html; charset=utf-8")),                     // 52
+    ("content-type", Some("text/plain")),                                   // 53
+    ("content-type", Some("text/plain;charset=utf-8")),                     // 54
+    ("range", Some("bytes=0-")),                                            // 55
+    ("strict-transport-security", Some("max-age=31536000")),                 // 56
+    ("strict-transport-security", Some("max-age=31536000; includesubdomains")), // 57
+    ("strict-transport-security",
+        Some("max-age=31536000; includesubdomains; preload")),              // 58
+    ("vary", Some("accept-encoding")),                                       // 59
+    ("vary", Some("origin")),                                                // 60
+    ("x-content-type-options", Some("nosniff")),                             // 61
+    ("x-xss-protection", Some("1; mode=block")),                             // 62
+    (":status", Some("100")),                                                // 63
+    (":status", Some("204")),                                                // 64
+    (":status", Some("206")),                                                // 65
+    (":status", Some("302")),                                                // 66
+    (":status", Some("400")),                                                // 67
+    (":status", Some("403")),                                                // 68
+    (":status", Some("421")),                                                // 69
+    (":status", Some("425")),                                                // 70
+    (":status", Some("500")),                                                // 71
+    ("accept-language", None),                                               // 72
+    ("access-control-allow-credentials", Some("FALSE")),                     // 73
+    ("access-control-allow-credentials", Some("TRUE")),                      // 74
+    ("access-control-allow-headers", Some("*")),                             // 75
+    ("access-control-allow-methods", Some("get")),                           // 76
+    ("access-control-allow-methods", Some("get, post, options")),           // 77
+    ("access-control-allow-methods", Some("options")),                       // 78
+    ("access-control-expose-headers", Some("content-length")),               // 79
+    ("access-control-request-headers", Some("content-type")),                // 80
+    ("access-control-request-method", Some("get")),                         // 81
+    ("access-control-request-method", Some("post")),                        // 82
+    ("alt-svc", Some("clear")),                                              // 83
+    ("authorization", None),                                                 // 84
+    ("content-security-policy",
+        Some("script-src 'none'; object-src 'none'; base-uri 'none'")),     // 85
+    ("early-data", Some("1")),                                               // 86
+    ("expect-ct", None),                                                     // 87
+    ("forwarded", None),                                                     // 88
+    ("if-range", None),                                                     // 89
+    ("origin", None),                                                       // 90
+    ("purpose", Some("prefetch")),                                          // 91
+    ("server", None),                                                       // 92
+    ("timing-allow-origin", Some("*")),                                     // 93
+    ("upgrade-insecure-requests", Some("1")),                               // 94
+    ("user-agent", None),                                                   // 95
+    ("x-forwarded-for", None),                                              // 96
+    ("x-frame-options", Some("deny")),                                      // 97
+    ("x-frame-options", Some("sameorigin")),                                // 98
+];
+
+/// Looks up `name` (and, if present, `value`) in the static table.
+///
+/// Returns `Some((idx, true))` when both the name and value matched an
+/// entry, or `Some((idx, false))` when only the name matched.
+pub(crate) fn lookup(name: &str, value: Option<&str>) -> Option<(usize, bool)> {
+    let mut name_only = None;
+
+    for (idx, &(n, v)) in STATIC_TABLE.iter().enumerate() {
+        if n != name {
+            continue;
+        }
+
+        if v.is_some() && v == value {
+            return Some((idx, true));
+        }
+
+        if name_only.is_none() {
+            name_only = Some((idx, false));
+        }
+    }
+
+    name_only
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup_exact_match() {
+        assert_eq!(Some((1, true)), lookup(":path", Some("/")));
+    }
+
+    #[test]
+    fn test_lookup_name_only_match() {
+        // `:path` exists with value "/" (exact) but not "/foo"; the name
+        // should still be found for a literal-with-name-reference.
+        assert_eq!(Some((1, false)), lookup(":path", Some("/foo")));
+    }
+
+    #[test]
+    fn test_lookup_no_match() {
+        assert_eq!(None, lookup("x-custom", Some("v")));
+    }
+}